@@ -58,6 +58,15 @@ type ctxt = {
      */
     mut queued_locals: [ast::node_id],
 
+    /*
+     * A list of AST type node IDs whose inferred region must be patched to
+     * the next block we traverse. This is used for `&`-references that
+     * appear in the pattern or guard of an `alt` arm: we don't know the
+     * region of the arm's block until we reach it, so we defer the same way
+     * `queued_locals` defers local bindings.
+     */
+    mut queued_region_tys: [ast::node_id],
+
     parent: parent,
 
     /* True if we're within the pattern part of an alt, false otherwise. */
@@ -75,7 +84,13 @@ type ctxt = {
 fn scope_contains(region_map: @region_map, superscope: ast::node_id,
                   subscope: ast::node_id) -> bool {
     let mut subscope = subscope;
+    let seen = map::int_hash();
     while superscope != subscope {
+        if seen.contains_key(subscope) {
+            fail "cycle in region map parents: scope " +
+                 int::str(subscope) + " is its own ancestor";
+        }
+        seen.insert(subscope, ());
         alt region_map.parents.find(subscope) {
             none { ret false; }
             some(scope) { subscope = scope; }
@@ -91,10 +106,17 @@ fn nearest_common_ancestor(region_map: @region_map, scope_a: ast::node_id,
                     -> [ast::node_id] {
         let mut result = [scope];
         let mut scope = scope;
+        let seen = map::int_hash();
+        seen.insert(scope, ());
         loop {
             alt region_map.parents.find(scope) {
                 none { ret result; }
                 some(superscope) {
+                    if seen.contains_key(superscope) {
+                        fail "cycle in region map parents: scope " +
+                             int::str(superscope) + " is its own ancestor";
+                    }
+                    seen.insert(superscope, ());
                     result += [superscope];
                     scope = superscope;
                 }
@@ -120,11 +142,17 @@ fn nearest_common_ancestor(region_map: @region_map, scope_a: ast::node_id,
     ret some(a_ancestors[a_index + 1u]);
 }
 
-fn get_inferred_region(cx: ctxt, sp: syntax::codemap::span) -> ty::region {
+fn get_inferred_region(cx: ctxt, sp: syntax::codemap::span,
+                       ty_id: ast::node_id) -> ty::region {
     // We infer to the caller region if we're at item scope
     // and to the block region if we're at block scope.
     //
-    // TODO: What do we do if we're in an alt?
+    // If we're in the pattern or guard of an `alt` arm, we don't yet know
+    // which block the arm's bindings will be parented to, so we queue this
+    // type up to be patched once `resolve_block` reaches that block.
+    if cx.in_alt {
+        vec::push(cx.queued_region_tys, ty_id);
+    }
 
     ret alt cx.parent {
         pa_fn_item(_) | pa_nested_fn(_) {
@@ -139,7 +167,7 @@ fn get_inferred_region(cx: ctxt, sp: syntax::codemap::span) -> ty::region {
 }
 
 fn resolve_ty(ty: @ast::ty, cx: ctxt, visitor: visit::vt<ctxt>) {
-    let inferred_region = get_inferred_region(cx, ty.span);
+    let inferred_region = get_inferred_region(cx, ty.span, ty.id);
     cx.region_map.ast_type_to_inferred_region.insert(ty.id, inferred_region);
 
     alt ty.node {
@@ -183,6 +211,20 @@ fn resolve_ty(ty: @ast::ty, cx: ctxt, visitor: visit::vt<ctxt>) {
                                                      "context");
                                 }
                                 pa_block(_) {
+                                    // NOTE(Llandy3d/rust#chunk0-2): the
+                                    // backlog asked for block-scoped named
+                                    // region declarations here (binding
+                                    // `name` via a new `ast::decl_region`
+                                    // statement, resolved to
+                                    // `ty::re_block(block_id)`). That
+                                    // requires a new `syntax::ast` node
+                                    // plus parser/printer/fold/visit
+                                    // support, none of which exist in this
+                                    // tree (it contains only
+                                    // `middle/region.rs`), so there's
+                                    // nothing here to wire this arm up to.
+                                    // Left as the baseline "unknown region"
+                                    // error pending that AST work landing.
                                     cx.sess.span_err(ty.span,
                                                      "unknown region `" +
                                                      ident + "`");
@@ -228,15 +270,25 @@ fn resolve_block(blk: ast::blk, cx: ctxt, visitor: visit::vt<ctxt>) {
         cx.region_map.local_blocks.insert(local_id, blk.node.id);
     }
 
+    // Patch the inferred region of any `&`-types queued by an enclosing
+    // `alt` arm's pattern or guard to this block, now that we know it.
+    for ty_id in cx.queued_region_tys {
+        let ast_type_to_inferred_region = cx.region_map.ast_type_to_inferred_region;
+        ast_type_to_inferred_region.insert(ty_id, ty::re_block(blk.node.id));
+    }
+
     // Descend.
     let new_cx: ctxt = {parent: pa_block(blk.node.id),
                         mut queued_locals: [],
+                        mut queued_region_tys: [],
                         in_alt: false with cx};
     visit::visit_block(blk, new_cx, visitor);
 }
 
 fn resolve_arm(arm: ast::arm, cx: ctxt, visitor: visit::vt<ctxt>) {
-    let new_cx: ctxt = {mut queued_locals: [], in_alt: true with cx};
+    let new_cx: ctxt = {mut queued_locals: [],
+                        mut queued_region_tys: [],
+                        in_alt: true with cx};
     visit::visit_arm(arm, new_cx, visitor);
 }
 
@@ -296,6 +348,38 @@ fn resolve_expr(expr: @ast::expr, cx: ctxt, visitor: visit::vt<ctxt>) {
             }
             visit::visit_expr(expr, cx, visitor);
         }
+        ast::expr_while(cond, body) {
+            // The condition is re-evaluated on every iteration, so treat it
+            // as though it lives inside the body: an rvalue borrowed there
+            // shouldn't outlive a single iteration.
+            record_parent(cx, body.node.id);
+            let body_cx = {parent: pa_block(body.node.id) with cx};
+            visit::visit_expr(cond, body_cx, visitor);
+            visit::visit_block(body, cx, visitor);
+        }
+        ast::expr_do_while(body, cond) {
+            record_parent(cx, body.node.id);
+            let body_cx = {parent: pa_block(body.node.id) with cx};
+            visit::visit_expr(cond, body_cx, visitor);
+            visit::visit_block(body, cx, visitor);
+        }
+        ast::expr_loop(body) {
+            // No condition to re-scope, but the body still needs its own
+            // parent recorded so a temporary borrowed inside one iteration
+            // can't be mistaken for living in the enclosing block.
+            record_parent(cx, body.node.id);
+            visitor.visit_block(body, cx, visitor);
+        }
+        ast::expr_for(decl, seq, body) {
+            // The sequence is only evaluated once, up front, so it keeps
+            // the enclosing scope; only the per-iteration loop variable and
+            // the body itself are scoped to one iteration.
+            record_parent(cx, body.node.id);
+            visitor.visit_expr(seq, cx, visitor);
+            let body_cx = {parent: pa_block(body.node.id) with cx};
+            visitor.visit_local(decl, body_cx, visitor);
+            visitor.visit_block(body, cx, visitor);
+        }
         _ { visit::visit_expr(expr, cx, visitor); }
     }
 }
@@ -351,6 +435,7 @@ fn resolve_crate(sess: session, def_map: resolve::def_map, crate: @ast::crate)
                                   rvalue_to_block: map::int_hash()},
                     mut bindings: @list::nil,
                     mut queued_locals: [],
+                    mut queued_region_tys: [],
                     parent: pa_crate,
                     in_alt: false,
                     in_typeclass: false,
@@ -366,6 +451,42 @@ fn resolve_crate(sess: session, def_map: resolve::def_map, crate: @ast::crate)
         with *visit::default_visitor()
     });
     visit::visit_crate(*crate, cx, visitor);
+    check_region_map(sess, cx.region_map);
     ret cx.region_map;
 }
 
+// Walks the finished region map and checks that `parents` forms a forest
+// with no cycles. Without this pass, a malformed map would only be caught
+// when `scope_contains` or `ancestors_of` later hits its own cycle guard;
+// catching it here, once, up front gives a much better diagnostic.
+//
+// Called automatically at the end of `resolve_crate`.
+//
+// NOTE(Llandy3d/rust#chunk0-4): the backlog also asked for an optional
+// human-readable dump of the map, gated behind a session debugging flag.
+// `driver::session` isn't part of this tree, so there's no real flag to
+// gate it behind and no driver to call it from; a dump function with
+// nothing wiring it in would just be dead code. Dropping that half of the
+// request rather than shipping an uncalled function.
+fn check_region_map(sess: session, region_map: @region_map) {
+    let visited = map::int_hash();
+    region_map.parents.keys({|child|
+        if visited.contains_key(child) { ret; }
+
+        let path = map::int_hash();
+        let mut scope = child;
+        loop {
+            if path.contains_key(scope) {
+                sess.bug("cycle in region map: scope " + int::str(scope) +
+                         " is its own ancestor");
+            }
+            path.insert(scope, ());
+            visited.insert(scope, ());
+            alt region_map.parents.find(scope) {
+                none { break; }
+                some(parent_scope) { scope = parent_scope; }
+            }
+        }
+    });
+}
+